@@ -5,19 +5,84 @@ use std::fs;
 use std::io;
 use std::path::Path;
 
-/// Return the names of files/folders inside a directory.
+/// The kind of a non-regular file, e.g. a socket or a device node.
 ///
-/// Names are lowercased for easy comparisons.
-///
-fn get_names_in_directory(dir: &Path) -> io::Result<HashSet<OsString>> {
+/// These are never safe to treat as deletable cruft, so they get a more
+/// specific `Reason` than "directory is not empty".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecialFileKind {
+    Socket,
+    Fifo,
+    BlockDevice,
+    CharDevice,
+}
+
+impl fmt::Display for SpecialFileKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SpecialFileKind::Socket => "socket",
+            SpecialFileKind::Fifo => "fifo",
+            SpecialFileKind::BlockDevice => "block device",
+            SpecialFileKind::CharDevice => "character device",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(unix)]
+fn special_file_kind(file_type: fs::FileType) -> Option<SpecialFileKind> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if file_type.is_socket() {
+        Some(SpecialFileKind::Socket)
+    } else if file_type.is_fifo() {
+        Some(SpecialFileKind::Fifo)
+    } else if file_type.is_block_device() {
+        Some(SpecialFileKind::BlockDevice)
+    } else if file_type.is_char_device() {
+        Some(SpecialFileKind::CharDevice)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn special_file_kind(_file_type: fs::FileType) -> Option<SpecialFileKind> {
+    None
+}
+
+/// The contents of a directory, as seen by `can_be_deleted`.
+struct DirectoryContents {
+    /// Names of every entry, lowercased for easy comparisons.
+    names: HashSet<OsString>,
+
+    /// Entries which are sockets, FIFOs, or device files, and so are never
+    /// safe to delete.
+    special_files: Vec<(OsString, SpecialFileKind)>,
+}
+
+/// Return the names of files/folders inside a directory, and flag any
+/// entries which are special files (sockets, FIFOs, device nodes).
+fn get_names_in_directory(dir: &Path) -> io::Result<DirectoryContents> {
     let mut names = Vec::new();
+    let mut special_files = Vec::new();
 
     for entry in fs::read_dir(dir)? {
         let entry = entry?;
+
+        if let Ok(file_type) = entry.file_type() {
+            if let Some(kind) = special_file_kind(file_type) {
+                special_files.push((entry.file_name(), kind));
+            }
+        }
+
         names.push(entry.file_name().to_ascii_lowercase());
     }
 
-    Ok(HashSet::from_iter(names))
+    Ok(DirectoryContents {
+        names: HashSet::from_iter(names),
+        special_files,
+    })
 }
 
 /// Returns True if this path any ancestor is a `.git` folder,
@@ -40,6 +105,7 @@ pub enum Reason {
     NotEmpty(Vec<OsString>),
     InGitRepository,
     CannotListContents(io::Error),
+    ContainsSpecialFiles(Vec<(OsString, SpecialFileKind)>),
 }
 
 impl fmt::Display for Reason {
@@ -71,12 +137,63 @@ impl fmt::Display for Reason {
             Reason::InGitRepository => {
                 write!(f, "directory is inside a .git repository")
             }
+
+            Reason::ContainsSpecialFiles(entries) => {
+                write!(
+                    f,
+                    "directory contains {} special file{} which won't be deleted:",
+                    entries.len(),
+                    if entries.len() == 1 { "" } else { "s" }
+                )?;
+
+                // Sort the entries for consistent output.
+                let mut sorted: Vec<_> = entries.iter().collect();
+                sorted.sort_by_key(|(name, _)| name.to_string_lossy());
+
+                for (name, kind) in sorted {
+                    write!(f, "\n  - {} ({})", name.to_string_lossy(), kind)?;
+                }
+
+                Ok(())
+            }
         }
     }
 }
 
+/// The names of entries which are safe to delete by default.
+///
+/// *  `.DS_Store` stores some folder attributes used for showing the folder
+///    in the Finder, which I don't need to keep
+/// *  `.ipynb_checkpoints` is a folder used by Jupyter Notebooks, but not
+///    important if I've deleted the notebooks
+/// *  `.jekyll-cache` is a cache directory used by Jekyll sites, but
+///    can be easily regenerated and will be rebuilt regularly as part
+///    of the Jekyll build process
+/// *  `.venv` is the name I use for virtual environments, which I can
+///    easily regenerate if necessary
+/// *  `__pycache__` is the bytecode cache in Python projects, which is
+///    pointless if the original Python files have been removed
+/// *  `Thumbs.db` is a file that contains thumbnails on Windows systems
+///
+/// Users can extend (or replace) this list with a config file; see the
+/// `config` module.
+pub const DEFAULT_DELETABLE_NAMES: &[&str] = &[
+    ".ds_store",
+    ".ipynb_checkpoints",
+    ".jekyll-cache",
+    ".venv",
+    "__pycache__",
+    "desktop.ini",
+    "thumbs.db",
+];
+
 /// can_be_deleted checks whether a directory can be deleted.
-pub fn can_be_deleted(dir_path: &Path) -> DeleteDecision {
+///
+/// `deletable_names` is the (lowercased) set of entries which are safe to
+/// delete; a directory is safe to delete if the ONLY things it contains are
+/// these entries, any other entry should block the directory from being
+/// deleted.
+pub fn can_be_deleted(dir_path: &Path, deletable_names: &HashSet<OsString>) -> DeleteDecision {
     // Don't delete subfolders of a `.git` directory.
     //
     // For example, if you delete `.git/refs`, then Git can't detect
@@ -95,39 +212,17 @@ pub fn can_be_deleted(dir_path: &Path) -> DeleteDecision {
         return DeleteDecision::CannotDelete(Reason::InGitRepository);
     }
 
-    // This is the list of entries which I consider safe to delete.
-    //
-    // *  .DS_Store stores some folder attributes used for showing the folder
-    //    in the Finder, which I don't need to keep
-    // *  `.ipynb_checkpoints` is a folder used by Jupyter Notebooks, but not
-    //    important if I've deleted the notebooks
-    // *  `.jekyll-cache` is a cache directory used by Jekyll sites, but
-    //    can be easily regenerated and will be rebuilt regularly as part
-    //    of the Jekyll build process
-    // *  `.venv` is the name I use for virtual environments, which I can
-    //    easily regenerate if necessary
-    // *  `__pycache__` is the bytecode cache in Python projects, which is
-    //    pointless if the original Python files have been removed
-    // *  `Thumbs.db` is a file that contains thumbnails on Windows systems
-    //
-    // A directory is safe to delete if the ONLY things it contains are these entries;
-    // any other entry should block the directory from being deleted.
-    //
-    let deletable_names = HashSet::from([
-        OsString::from(".ds_store"),
-        OsString::from(".ipynb_checkpoints"),
-        OsString::from(".jekyll-cache"),
-        OsString::from(".venv"),
-        OsString::from("__pycache__"),
-        OsString::from("desktop.ini"),
-        OsString::from("thumbs.db"),
-    ]);
-
     match get_names_in_directory(dir_path) {
-        Ok(names) if names.is_subset(&deletable_names) => DeleteDecision::CanDelete,
-        Ok(names) => {
-            let remaining_entries: Vec<OsString> =
-                names.difference(&deletable_names).cloned().collect();
+        Ok(contents) if !contents.special_files.is_empty() => {
+            DeleteDecision::CannotDelete(Reason::ContainsSpecialFiles(contents.special_files))
+        }
+        Ok(contents) if contents.names.is_subset(deletable_names) => DeleteDecision::CanDelete,
+        Ok(contents) => {
+            let remaining_entries: Vec<OsString> = contents
+                .names
+                .difference(deletable_names)
+                .cloned()
+                .collect();
             DeleteDecision::CannotDelete(Reason::NotEmpty(remaining_entries))
         }
         Err(e) => DeleteDecision::CannotDelete(Reason::CannotListContents(e)),
@@ -155,11 +250,15 @@ mod test_can_be_deleted {
         fs::write(&path, "this file is for testing").unwrap();
     }
 
+    fn default_deletable_names() -> HashSet<OsString> {
+        DEFAULT_DELETABLE_NAMES.iter().map(OsString::from).collect()
+    }
+
     #[test]
     fn a_dir_cant_be_deleted_if_we_cant_read_the_contents() {
         let dir_path = Path::new("/does/not/exist");
         assert!(matches!(
-            can_be_deleted(&dir_path),
+            can_be_deleted(&dir_path, &default_deletable_names()),
             DeleteDecision::CannotDelete(Reason::CannotListContents(_))
         ));
     }
@@ -172,7 +271,7 @@ mod test_can_be_deleted {
         create_dir(&dir_path);
 
         assert!(matches!(
-            can_be_deleted(&dir_path),
+            can_be_deleted(&dir_path, &default_deletable_names()),
             DeleteDecision::CanDelete
         ));
     }
@@ -186,7 +285,7 @@ mod test_can_be_deleted {
 
         create_file(dir_path.join("greeting.txt"));
 
-        match can_be_deleted(&dir_path) {
+        match can_be_deleted(&dir_path, &default_deletable_names()) {
             DeleteDecision::CannotDelete(Reason::NotEmpty(entries)) => {
                 assert_eq!(entries, vec![OsString::from("greeting.txt")]);
             }
@@ -206,7 +305,7 @@ mod test_can_be_deleted {
         create_file(dir_path.join(".DS_Store"));
 
         assert!(matches!(
-            can_be_deleted(&dir_path),
+            can_be_deleted(&dir_path, &default_deletable_names()),
             DeleteDecision::CanDelete
         ));
     }
@@ -220,7 +319,7 @@ mod test_can_be_deleted {
         create_file(dir_path.join(".DS_Store"));
         create_file(dir_path.join("greeting.txt"));
 
-        match can_be_deleted(&dir_path) {
+        match can_be_deleted(&dir_path, &default_deletable_names()) {
             DeleteDecision::CannotDelete(Reason::NotEmpty(entries)) => {
                 // `.DS_Store` is allowed, `greeting.txt` is not
                 assert_eq!(entries, vec![OsString::from("greeting.txt")]);
@@ -238,7 +337,7 @@ mod test_can_be_deleted {
         create_file(dir_path.join(".ds_store"));
 
         assert!(matches!(
-            can_be_deleted(&dir_path),
+            can_be_deleted(&dir_path, &default_deletable_names()),
             DeleteDecision::CanDelete
         ));
     }
@@ -251,7 +350,7 @@ mod test_can_be_deleted {
         create_dir(&git_dir);
 
         assert!(matches!(
-            can_be_deleted(&git_dir),
+            can_be_deleted(&git_dir, &default_deletable_names()),
             DeleteDecision::CannotDelete(Reason::InGitRepository)
         ));
     }
@@ -264,8 +363,45 @@ mod test_can_be_deleted {
         create_dir(&refs_dir);
 
         assert!(matches!(
-            can_be_deleted(&refs_dir),
+            can_be_deleted(&refs_dir, &default_deletable_names()),
             DeleteDecision::CannotDelete(Reason::InGitRepository)
         ));
     }
+
+    #[test]
+    #[cfg(unix)]
+    fn a_directory_containing_a_socket_cannot_be_deleted() {
+        let dir_path = test_dir();
+
+        create_dir(&dir_path);
+
+        let socket_path = dir_path.join("service.sock");
+        let _listener = std::os::unix::net::UnixListener::bind(&socket_path).unwrap();
+
+        match can_be_deleted(&dir_path, &default_deletable_names()) {
+            DeleteDecision::CannotDelete(Reason::ContainsSpecialFiles(entries)) => {
+                assert_eq!(
+                    entries,
+                    vec![(OsString::from("service.sock"), SpecialFileKind::Socket)]
+                );
+            }
+            other => panic!("unexpected decision: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn an_extra_deletable_name_is_treated_as_safe_to_delete() {
+        let dir_path = test_dir();
+
+        create_dir(&dir_path);
+        create_dir(&dir_path.join("node_modules"));
+
+        let mut deletable_names = default_deletable_names();
+        deletable_names.insert(OsString::from("node_modules"));
+
+        assert!(matches!(
+            can_be_deleted(&dir_path, &deletable_names),
+            DeleteDecision::CanDelete
+        ));
+    }
 }
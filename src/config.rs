@@ -0,0 +1,159 @@
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::can_be_deleted::DEFAULT_DELETABLE_NAMES;
+
+/// User-supplied overrides for the names `emptydir` treats as safe to delete.
+///
+/// These are usually loaded from `~/.config/emptydir/config.toml`, e.g.:
+///
+///     extra_deletable = ["node_modules", ".mypy_cache", "target"]
+///     exclude_defaults = false
+///
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct Config {
+    /// Extra names to treat as deletable cruft, on top of the built-in list.
+    #[serde(default)]
+    pub extra_deletable: Vec<String>,
+
+    /// If true, don't fall back to `can_be_deleted::DEFAULT_DELETABLE_NAMES` -
+    /// only use `extra_deletable`.
+    #[serde(default)]
+    pub exclude_defaults: bool,
+}
+
+impl Config {
+    /// Load a `Config` from `path`.
+    ///
+    /// If `path` is given, it must exist. If `path` is `None`, this looks
+    /// for a config file at the default location
+    /// (`~/.config/emptydir/config.toml`); if nothing is there, it falls
+    /// back to the default (built-in-only) config.
+    pub fn load(path: Option<&Path>) -> io::Result<Config> {
+        match path {
+            Some(path) => Config::from_file(path),
+            None => match default_config_path() {
+                Some(path) if path.exists() => Config::from_file(&path),
+                _ => Ok(Config::default()),
+            },
+        }
+    }
+
+    fn from_file(path: &Path) -> io::Result<Config> {
+        let contents = fs::read_to_string(path)?;
+        toml::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// The resolved, lowercased set of names which are safe to delete,
+    /// combining the built-in defaults (unless `exclude_defaults` is set)
+    /// with `extra_deletable`.
+    pub fn deletable_names(&self) -> HashSet<OsString> {
+        let mut names: HashSet<OsString> = if self.exclude_defaults {
+            HashSet::new()
+        } else {
+            DEFAULT_DELETABLE_NAMES.iter().map(OsString::from).collect()
+        };
+
+        names.extend(
+            self.extra_deletable
+                .iter()
+                .map(|name| OsString::from(name.to_ascii_lowercase())),
+        );
+
+        names
+    }
+}
+
+fn default_config_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|dir| dir.join(".config").join("emptydir").join("config.toml"))
+}
+
+#[cfg(test)]
+mod test_config {
+    use super::*;
+
+    #[test]
+    fn the_default_config_only_uses_the_built_in_names() {
+        let config = Config::default();
+
+        let expected: HashSet<OsString> =
+            DEFAULT_DELETABLE_NAMES.iter().map(OsString::from).collect();
+
+        assert_eq!(config.deletable_names(), expected);
+    }
+
+    #[test]
+    fn extra_deletable_names_are_added_to_the_defaults() {
+        let config = Config {
+            extra_deletable: vec![String::from("node_modules")],
+            exclude_defaults: false,
+        };
+
+        assert!(config
+            .deletable_names()
+            .contains(&OsString::from("node_modules")));
+        assert!(config
+            .deletable_names()
+            .contains(&OsString::from(".ds_store")));
+    }
+
+    #[test]
+    fn exclude_defaults_drops_the_built_in_names() {
+        let config = Config {
+            extra_deletable: vec![String::from("node_modules")],
+            exclude_defaults: true,
+        };
+
+        let names = config.deletable_names();
+        assert!(names.contains(&OsString::from("node_modules")));
+        assert!(!names.contains(&OsString::from(".ds_store")));
+    }
+
+    #[test]
+    fn extra_deletable_names_are_lowercased() {
+        let config = Config {
+            extra_deletable: vec![String::from("Node_Modules")],
+            exclude_defaults: false,
+        };
+
+        assert!(config
+            .deletable_names()
+            .contains(&OsString::from("node_modules")));
+    }
+
+    #[test]
+    fn loading_a_missing_explicit_config_file_is_an_error() {
+        let path = Path::new("/does/not/exist/config.toml");
+        assert!(Config::load(Some(path)).is_err());
+    }
+
+    #[test]
+    fn loading_a_config_file_parses_its_contents() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("config.toml");
+
+        fs::write(
+            &path,
+            r#"
+                extra_deletable = ["node_modules", "target"]
+                exclude_defaults = true
+            "#,
+        )
+        .unwrap();
+
+        let config = Config::load(Some(&path)).unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                extra_deletable: vec![String::from("node_modules"), String::from("target")],
+                exclude_defaults: true,
+            }
+        );
+    }
+}
@@ -1,62 +1,224 @@
+use std::collections::HashSet;
+use std::ffi::OsString;
 use std::fs;
-use std::path::Path;
+use std::io;
+use std::path::{Path, PathBuf};
 
 use colored::*;
+use rayon::prelude::*;
 use walkdir::WalkDir;
 
 #[derive(Debug, PartialEq)]
 pub struct EmptydirResult {
     pub count_deleted: u32,
     pub count_errors: u32,
+    pub used_trash: bool,
+    pub bytes_reclaimed: u64,
+}
+
+fn can_delete(path: &Path, deletable_names: &HashSet<OsString>) -> bool {
+    matches!(
+        crate::can_be_deleted::can_be_deleted(path, deletable_names),
+        crate::can_be_deleted::DeleteDecision::CanDelete
+    )
+}
+
+/// Add up the size of every file inside a directory, including its subdirectories.
+fn dir_size(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Remove a directory, either permanently or by sending it to the OS trash.
+fn remove_dir(path: &Path, use_trash: bool) -> io::Result<()> {
+    if use_trash {
+        trash::delete(path).map_err(|e| {
+            // Preserve `NotFound` so callers can treat an already-vanished
+            // directory as a no-op success rather than a genuine failure.
+            let kind = if !path.exists() {
+                io::ErrorKind::NotFound
+            } else {
+                io::ErrorKind::Other
+            };
+            io::Error::new(kind, e.to_string())
+        })
+    } else {
+        fs::remove_dir_all(path)
+    }
 }
 
 /// Recurse through a given root directory, and delete any "empty" directories.
 ///
+/// If `boundary` is given, the upward walk through `root`'s ancestors will
+/// stop as soon as it reaches `boundary`, and `boundary` itself is never
+/// deleted. `boundary` must be an ancestor of `root`; if it isn't, nothing
+/// is deleted and the result reports an error.
+///
+/// If `use_trash` is true, directories are sent to the platform trash/
+/// recycle bin instead of being permanently deleted.
+///
+/// Each downward pass classifies (and sizes) candidate directories in
+/// parallel, but a directory that only becomes empty because one of its
+/// descendants was deleted earlier in the *same* pass won't be picked up
+/// until the next one - so passes repeat until a full pass deletes
+/// nothing, which is how a chain of "wrapper" directories around some
+/// cruft (e.g. `project/wrapper/.venv`) collapses in one `emptydir` call.
+/// Within a pass, every candidate is classified before any is deleted,
+/// and the deletions then run deepest-first.
+///
+/// `deletable_names` is the (lowercased) set of entries which are safe to
+/// delete; see `can_be_deleted::DEFAULT_DELETABLE_NAMES` and the `config`
+/// module for how this is usually built.
+///
 /// Returns the number of directories deleted.
 ///
-pub fn emptydir(root: &Path) -> EmptydirResult {
-    let directories_to_delete = WalkDir::new(root)
-        .contents_first(true)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_dir())
-        .filter(|e| crate::can_be_deleted::can_be_deleted(e.path()));
+pub fn emptydir(
+    root: &Path,
+    boundary: Option<&Path>,
+    use_trash: bool,
+    deletable_names: &HashSet<OsString>,
+) -> EmptydirResult {
+    // Canonicalize so the boundary check and the upward walk work correctly
+    // even when `root` is relative (e.g. the default `.`) or takes a
+    // winding path through `..` components; if canonicalization fails
+    // (e.g. the path doesn't exist), fall back to the path as given.
+    let canonical_root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    let canonical_boundary =
+        boundary.map(|b| fs::canonicalize(b).unwrap_or_else(|_| b.to_path_buf()));
+
+    if let Some(ref canonical_boundary) = canonical_boundary {
+        if !canonical_root
+            .ancestors()
+            .any(|ancestor| ancestor == canonical_boundary)
+        {
+            let message = format!(
+                "Boundary {} is not an ancestor of {}",
+                boundary.unwrap().display(),
+                root.display()
+            );
+            eprintln!("{}", message.red());
+            return EmptydirResult {
+                count_deleted: 0,
+                count_errors: 1,
+                used_trash: use_trash,
+                bytes_reclaimed: 0,
+            };
+        }
+    }
 
     let mut count_deleted: u32 = 0;
     let mut count_errors: u32 = 0;
+    let mut bytes_reclaimed: u64 = 0;
+
+    loop {
+        // Collect every candidate directory up front, then classify them
+        // in parallel, since that's the I/O-bound part of the work. Walk
+        // from `canonical_root` rather than `root` itself - e.g. `root`
+        // may be `.`, and `fs::remove_dir_all(".")` fails with "Invalid
+        // argument" even though removing the same directory by its
+        // absolute path works fine.
+        let candidate_dirs: Vec<PathBuf> = WalkDir::new(&canonical_root)
+            .contents_first(true)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_dir())
+            .map(|e| e.path().to_path_buf())
+            .filter(|path| canonical_boundary.as_ref() != Some(path))
+            .collect();
+
+        let mut deletable_dirs: Vec<PathBuf> = candidate_dirs
+            .par_iter()
+            .filter(|path| can_delete(path, deletable_names))
+            .cloned()
+            .collect();
+
+        if deletable_dirs.is_empty() {
+            break;
+        }
 
-    for dir in directories_to_delete {
-        match fs::remove_dir_all(dir.path()) {
-            Ok(_) => {
-                println!("{}", dir.path().display());
-                count_deleted += 1;
-            }
-            Err(e) => {
-                let message = format!(
-                    "Tried to delete {}, but got error: {}",
-                    dir.path().display(),
-                    e
-                );
-                eprintln!("{}", message.red());
-                count_errors += 1;
-            }
-        };
+        // A directory and one of its ancestors can both independently
+        // satisfy `can_delete` (e.g. `dir/__pycache__/.DS_Store` - both
+        // `dir` and `dir/__pycache__` only contain deletable entries).
+        // Deleting the outer one already removes the inner one, so drop
+        // any candidate nested inside another candidate before
+        // sizing/counting them, or we'd count the same bytes twice.
+        let deletable_set: HashSet<PathBuf> = deletable_dirs.iter().cloned().collect();
+        deletable_dirs.retain(|path| {
+            !path
+                .ancestors()
+                .skip(1)
+                .any(|ancestor| deletable_set.contains(ancestor))
+        });
+
+        let mut directories_to_delete: Vec<(PathBuf, u64)> = deletable_dirs
+            .par_iter()
+            .map(|path| (path.clone(), dir_size(path)))
+            .collect();
+
+        // Delete deepest-first, matching the `contents_first` order of
+        // the walk.
+        directories_to_delete.sort_by_key(|(path, _)| std::cmp::Reverse(path.components().count()));
+
+        let mut deleted_this_pass: u32 = 0;
+
+        for (path, size) in &directories_to_delete {
+            match remove_dir(path, use_trash) {
+                Ok(_) => {
+                    println!("{}", path.display());
+                    count_deleted += 1;
+                    deleted_this_pass += 1;
+                    bytes_reclaimed += size;
+                }
+                // Someone else already removed it - that's the end state
+                // we wanted, so it's not an error.
+                Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+                Err(e) => {
+                    let message =
+                        format!("Tried to delete {}, but got error: {}", path.display(), e);
+                    eprintln!("{}", message.red());
+                    count_errors += 1;
+                }
+            };
+        }
+
+        // If nothing was actually deleted this pass (e.g. every candidate
+        // failed with a permissions error), another pass would just find
+        // the same candidates again - stop instead of looping forever.
+        if deleted_this_pass == 0 {
+            break;
+        }
     }
 
     // Now work our way upward through the parent directories, and
-    // delete any of those which are empty.
-    let mut current_parent = root.parent();
+    // delete any of those which are empty, stopping at the boundary
+    // (if one was given) without ever deleting it.
+    let mut current_parent = canonical_root.parent().map(|p| p.to_path_buf());
 
     while let Some(parent) = current_parent {
-        if !crate::can_be_deleted::can_be_deleted(parent) {
+        if let Some(ref canonical_boundary) = canonical_boundary {
+            if parent == *canonical_boundary || !parent.starts_with(canonical_boundary) {
+                break;
+            }
+        }
+
+        if !can_delete(&parent, deletable_names) {
             break;
         }
 
-        match fs::remove_dir_all(parent) {
+        let size = dir_size(&parent);
+
+        match remove_dir(&parent, use_trash) {
             Ok(_) => {
                 println!("{}", parent.display());
                 count_deleted += 1;
+                bytes_reclaimed += size;
             }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (),
             Err(e) => {
                 let message = format!("Tried to delete {}, but got error: {}", parent.display(), e);
                 eprintln!("{}", message.red());
@@ -64,12 +226,14 @@ pub fn emptydir(root: &Path) -> EmptydirResult {
             }
         };
 
-        current_parent = parent.parent();
+        current_parent = parent.parent().map(|p| p.to_path_buf());
     }
 
     EmptydirResult {
         count_deleted,
         count_errors,
+        used_trash: use_trash,
+        bytes_reclaimed,
     }
 }
 
@@ -77,9 +241,16 @@ pub fn emptydir(root: &Path) -> EmptydirResult {
 mod test_emptydir {
     use std::fs;
     use std::path::{Path, PathBuf};
+    use std::sync::Mutex;
 
     use super::*;
 
+    // `cargo test` runs tests concurrently in the same process, so any
+    // test which calls `std::env::set_current_dir` must hold this lock
+    // for as long as the cwd is changed, or it could race with another
+    // test that resolves a relative path.
+    static CWD_LOCK: Mutex<()> = Mutex::new(());
+
     fn test_dir() -> PathBuf {
         let tmp_dir = tempfile::tempdir().unwrap();
         let path = tmp_dir.path();
@@ -90,6 +261,13 @@ mod test_emptydir {
         fs::create_dir_all(dir).unwrap();
     }
 
+    fn default_deletable_names() -> HashSet<OsString> {
+        crate::can_be_deleted::DEFAULT_DELETABLE_NAMES
+            .iter()
+            .map(OsString::from)
+            .collect()
+    }
+
     fn create_file(path: &PathBuf) {
         create_dir(&path.parent().unwrap().to_path_buf());
         fs::write(&path, "this file is for testing").unwrap();
@@ -99,10 +277,12 @@ mod test_emptydir {
     fn it_doesnt_delete_my_do_not_backup() {
         let dir = Path::new("/Users/alexwlchan/Desktop/do not back up");
         assert_eq!(
-            emptydir(dir),
+            emptydir(dir, None, false, &default_deletable_names()),
             EmptydirResult {
                 count_deleted: 0,
-                count_errors: 0
+                count_errors: 0,
+                used_trash: false,
+                bytes_reclaimed: 0
             }
         );
     }
@@ -111,10 +291,12 @@ mod test_emptydir {
     fn it_doesnt_delete_a_non_existent_directory() {
         let dir = Path::new("/does/not/exist");
         assert_eq!(
-            emptydir(dir),
+            emptydir(dir, None, false, &default_deletable_names()),
             EmptydirResult {
                 count_deleted: 0,
-                count_errors: 0
+                count_errors: 0,
+                used_trash: false,
+                bytes_reclaimed: 0
             }
         );
     }
@@ -127,10 +309,12 @@ mod test_emptydir {
         create_dir(&dir);
 
         assert_eq!(
-            emptydir(&dir),
+            emptydir(&dir, None, false, &default_deletable_names()),
             EmptydirResult {
                 count_deleted: 1,
-                count_errors: 0
+                count_errors: 0,
+                used_trash: false,
+                bytes_reclaimed: 0
             }
         );
         assert_eq!(dir.exists(), false);
@@ -146,10 +330,12 @@ mod test_emptydir {
         create_file(&dir.join("greeting.txt"));
 
         assert_eq!(
-            emptydir(&dir),
+            emptydir(&dir, None, false, &default_deletable_names()),
             EmptydirResult {
                 count_deleted: 0,
-                count_errors: 0
+                count_errors: 0,
+                used_trash: false,
+                bytes_reclaimed: 0
             }
         );
         assert_eq!(dir.exists(), true);
@@ -187,10 +373,68 @@ mod test_emptydir {
         create_file(&dir.join(".DS_Store"));
 
         assert_eq!(
-            emptydir(&dir),
+            emptydir(&dir, None, false, &default_deletable_names()),
+            EmptydirResult {
+                count_deleted: 1,
+                count_errors: 0,
+                used_trash: false,
+                bytes_reclaimed: 4 * "this file is for testing".len() as u64
+            }
+        );
+        assert_eq!(dir.exists(), false);
+    }
+
+    #[test]
+    fn nested_deletable_directories_are_only_counted_once() {
+        let dir = test_dir();
+
+        //    .
+        //    └─ __pycache__/
+        //        └─ .DS_Store
+        //
+        // `dir` and `dir/__pycache__` both independently qualify as safe
+        // to delete (each one's own entries are a subset of
+        // `deletable_names`), but deleting `dir` already removes
+        // `__pycache__` along with it - it shouldn't be sized and counted
+        // as a second, separate deletion.
+        create_dir(&dir.join("__pycache__"));
+        create_file(&dir.join("__pycache__/.DS_Store"));
+
+        assert_eq!(
+            emptydir(&dir, None, false, &default_deletable_names()),
             EmptydirResult {
                 count_deleted: 1,
-                count_errors: 0
+                count_errors: 0,
+                used_trash: false,
+                bytes_reclaimed: "this file is for testing".len() as u64
+            }
+        );
+        assert_eq!(dir.exists(), false);
+    }
+
+    #[test]
+    fn a_chain_of_wrapper_directories_collapses_in_one_call() {
+        let dir = test_dir();
+
+        //    .
+        //    └─ w1/
+        //        └─ w2/
+        //            └─ .venv/
+        //
+        // `w2` only contains `.venv`, so it's safe to delete straight
+        // away, but `w1` and `dir` only become safe to delete once their
+        // child has already been removed.
+        // A single `emptydir` call should still collapse the whole chain,
+        // not just the innermost directory.
+        create_dir(&dir.join("w1").join("w2").join(".venv"));
+
+        assert_eq!(
+            emptydir(&dir, None, false, &default_deletable_names()),
+            EmptydirResult {
+                count_deleted: 3,
+                count_errors: 0,
+                used_trash: false,
+                bytes_reclaimed: 0
             }
         );
         assert_eq!(dir.exists(), false);
@@ -206,10 +450,12 @@ mod test_emptydir {
         create_file(&dir.join("greeting.txt"));
 
         assert_eq!(
-            emptydir(&dir),
+            emptydir(&dir, None, false, &default_deletable_names()),
             EmptydirResult {
                 count_deleted: 0,
-                count_errors: 0
+                count_errors: 0,
+                used_trash: false,
+                bytes_reclaimed: 0
             }
         );
         assert!(dir.exists());
@@ -247,14 +493,149 @@ mod test_emptydir {
         create_file(&dir.join("greeting.txt"));
 
         assert_eq!(
-            emptydir(&dir),
+            emptydir(&dir, None, false, &default_deletable_names()),
             EmptydirResult {
                 count_deleted: 1,
-                count_errors: 0
+                count_errors: 0,
+                used_trash: false,
+                bytes_reclaimed: 3 * "this file is for testing".len() as u64
             }
         );
         assert_eq!(dir.exists(), true);
         assert_eq!(subdir.exists(), false);
         assert!(dir.join("greeting.txt").exists());
     }
+
+    #[test]
+    fn it_stops_the_upward_walk_at_the_boundary() {
+        let dir = test_dir();
+        let subdir = dir.join("subdir");
+
+        create_dir(&subdir);
+
+        assert_eq!(
+            emptydir(&subdir, Some(&dir), false, &default_deletable_names()),
+            EmptydirResult {
+                count_deleted: 1,
+                count_errors: 0,
+                used_trash: false,
+                bytes_reclaimed: 0
+            }
+        );
+        assert_eq!(subdir.exists(), false);
+        assert_eq!(dir.exists(), true);
+    }
+
+    #[test]
+    fn it_refuses_to_run_if_the_boundary_is_not_an_ancestor_of_root() {
+        let dir = test_dir();
+        let other_dir = test_dir();
+
+        create_dir(&dir);
+
+        assert_eq!(
+            emptydir(&dir, Some(&other_dir), false, &default_deletable_names()),
+            EmptydirResult {
+                count_deleted: 0,
+                count_errors: 1,
+                used_trash: false,
+                bytes_reclaimed: 0
+            }
+        );
+        assert_eq!(dir.exists(), true);
+    }
+
+    #[test]
+    fn the_boundary_check_works_when_root_is_a_relative_path() {
+        let dir = test_dir();
+        let subdir = dir.join("subdir");
+
+        create_dir(&subdir);
+
+        // `root` defaults to `.`, which only ever has itself and `""` as
+        // ancestors - so the boundary check must canonicalize `root`
+        // before comparing it against `boundary`, or a relative root can
+        // never match an absolute boundary even when it genuinely is one
+        // of its ancestors.
+        let _guard = CWD_LOCK.lock().unwrap();
+        let original_dir = std::env::current_dir().unwrap();
+        std::env::set_current_dir(&subdir).unwrap();
+        let result = emptydir(
+            Path::new("."),
+            Some(&dir),
+            false,
+            &default_deletable_names(),
+        );
+        std::env::set_current_dir(&original_dir).unwrap();
+
+        assert_eq!(
+            result,
+            EmptydirResult {
+                count_deleted: 1,
+                count_errors: 0,
+                used_trash: false,
+                bytes_reclaimed: 0
+            }
+        );
+        assert_eq!(subdir.exists(), false);
+        assert_eq!(dir.exists(), true);
+    }
+
+    #[test]
+    fn removing_an_already_vanished_dir_reports_not_found() {
+        let dir = test_dir();
+
+        // Don't create the directory, so it's already gone by the time
+        // we try to remove it - as if another process had beaten us to it.
+        // `emptydir`'s deletion loop treats this `NotFound` as a no-op
+        // success rather than a genuine error.
+        match remove_dir(&dir, false) {
+            Err(e) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+            Ok(_) => panic!("expected a NotFound error"),
+        }
+    }
+
+    #[test]
+    fn it_moves_an_empty_dir_to_the_trash_instead_of_deleting_it() {
+        let dir = test_dir();
+        create_dir(&dir);
+
+        assert_eq!(
+            emptydir(&dir, None, true, &default_deletable_names()),
+            EmptydirResult {
+                count_deleted: 1,
+                count_errors: 0,
+                used_trash: true,
+                bytes_reclaimed: 0
+            }
+        );
+        assert_eq!(dir.exists(), false);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn remove_dir_with_use_trash_actually_uses_the_trash_can() {
+        let dir = test_dir();
+        let dir_name = dir.file_name().unwrap().to_owned();
+        create_dir(&dir);
+
+        remove_dir(&dir, true).unwrap();
+        assert_eq!(dir.exists(), false);
+
+        // On Linux, `trash::delete` follows the freedesktop.org trash
+        // spec and moves the directory into `$XDG_DATA_HOME/Trash/files`
+        // rather than removing it outright - check it actually landed
+        // there, so this test can't pass if `remove_dir` silently fell
+        // back to a permanent delete.
+        let trashed_dir = dirs::data_dir()
+            .unwrap()
+            .join("Trash")
+            .join("files")
+            .join(&dir_name);
+        assert!(trashed_dir.exists());
+
+        // Clean up after ourselves, so repeated test runs don't pollute
+        // the trash can.
+        let _ = fs::remove_dir_all(&trashed_dir);
+    }
 }
@@ -4,9 +4,11 @@ use std::path::Path;
 
 use clap::Parser;
 use colored::*;
+use humansize::{format_size, DECIMAL};
 use num_format::{Locale, ToFormattedString};
 
 mod can_be_deleted;
+mod config;
 mod emptydir;
 
 #[derive(Parser)]
@@ -15,27 +17,69 @@ struct Cli {
     /// Path to the directory to inspect
     #[arg(default_value_t = String::from("."))]
     root: String,
+
+    /// Stop deleting empty ancestor directories once this path is reached.
+    /// Must be an ancestor of `root`; the boundary itself is never deleted.
+    #[arg(long)]
+    boundary: Option<String>,
+
+    /// Move directories to the trash/recycle bin instead of permanently
+    /// deleting them
+    #[arg(long)]
+    trash: bool,
+
+    /// Path to a config file listing extra names to treat as deletable.
+    /// Defaults to `~/.config/emptydir/config.toml`, if it exists.
+    #[arg(long)]
+    config: Option<String>,
 }
 
 fn main() -> Result<(), std::io::Error> {
     let cli = Cli::parse();
 
+    let config = config::Config::load(cli.config.as_deref().map(Path::new))?;
+    let deletable_names = config.deletable_names();
+
     let root = Path::new(&cli.root);
-    let result = emptydir::emptydir(root);
+    let boundary = cli.boundary.as_deref().map(Path::new);
+    let result = emptydir::emptydir(root, boundary, cli.trash, &deletable_names);
+
+    let verb = if result.used_trash {
+        "trashed"
+    } else {
+        "deleted"
+    };
 
     match (result.count_deleted, result.count_errors) {
-        (0, 0) => match can_be_deleted::can_be_deleted(&root) {
+        (0, 0) => match can_be_deleted::can_be_deleted(&root, &deletable_names) {
             can_be_deleted::DeleteDecision::CannotDelete(reason) => {
                 eprintln!("{}", reason.to_string().red());
             }
             _ => (),
         },
-        (0, _) => println!("{}", "Unable to delete empty directories".red()),
-        (1, _) => println!("{}", "1 directory deleted".green()),
+        (0, _) => println!(
+            "{}",
+            format!(
+                "Unable to {} empty directories",
+                if result.used_trash { "trash" } else { "delete" }
+            )
+            .red()
+        ),
+        (1, _) => println!(
+            "{}",
+            format!(
+                "1 directory {} ({} reclaimed)",
+                verb,
+                format_size(result.bytes_reclaimed, DECIMAL)
+            )
+            .green()
+        ),
         _ => {
             let message = format!(
-                "{} directories deleted",
-                result.count_deleted.to_formatted_string(&Locale::en)
+                "{} directories {} ({} reclaimed)",
+                result.count_deleted.to_formatted_string(&Locale::en),
+                verb,
+                format_size(result.bytes_reclaimed, DECIMAL)
             );
             println!("{}", message.green());
         }